@@ -0,0 +1,218 @@
+//! The core substitution machinery: every term that can contain variables implements
+//! `Fold` so that binders (see [`crate::grammar::Binder`]) can be opened, closed, and
+//! substituted into generically.
+
+use std::cell::{Cell, RefCell};
+
+use crate::grammar::{Binder, BoundVar, DebruijnIndex, Parameter, ParameterKind, Variable};
+
+/// A closure invoked once per `Variable` encountered during a [`Fold::substitute`] walk.
+/// Returning `Some` replaces that occurrence; returning `None` leaves it untouched.
+pub type SubstitutionFn<'a> = &'a mut dyn FnMut(ParameterKind, &Variable) -> Option<Parameter>;
+
+pub trait Fold: Clone {
+    /// Replaces each free variable in `self` for which `substitution_fn` returns `Some`
+    /// with the returned parameter, leaving everything else untouched.
+    fn substitute(&self, substitution_fn: SubstitutionFn<'_>) -> Self;
+
+    /// Collects every variable that appears free in `self`.
+    fn free_variables(&self) -> Vec<Variable>;
+
+    /// Shifts every free variable in `self` in by one, as if one additional binder now
+    /// enclosed it.
+    fn shift_in(&self) -> Self;
+
+    /// Shifts every free variable in `self` in by `amount`, as if `amount` additional
+    /// binders now enclosed it. The default just applies [`Self::shift_in`] one binder at
+    /// a time; composite types that can adjust the amount in a single structural pass
+    /// (like [`Binder`]) override it to skip the repeated recursion.
+    fn shift_in_by(&self, amount: usize) -> Self {
+        let mut result = self.clone();
+        for _ in 0..amount {
+            result = result.shift_in();
+        }
+        result
+    }
+
+    /// The inverse of [`Self::shift_in_by`]: removes `amount` levels of enclosing binders
+    /// from every free variable in `self`, failing if some variable isn't free with
+    /// respect to all `amount` of them. The default applies `Variable::shift_out` one step
+    /// at a time, bailing out as soon as one escapes; composite types like [`Binder`]
+    /// override it to adjust the amount in a single pass.
+    fn shift_out_by(&self, amount: usize) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let failed = Cell::new(false);
+
+        let result = self.substitute(&mut |kind, variable| {
+            if !matches!(
+                variable,
+                Variable::BoundVar(BoundVar {
+                    debruijn: Some(_),
+                    ..
+                })
+            ) {
+                return None;
+            }
+
+            let mut shifted = variable.clone();
+            for _ in 0..amount {
+                match shifted.shift_out() {
+                    Some(next) => shifted = next,
+                    None => {
+                        failed.set(true);
+                        return None;
+                    }
+                }
+            }
+
+            match shifted {
+                Variable::BoundVar(bound_var) => Some(bound_var.into_parameter(kind)),
+                _ => None,
+            }
+        });
+
+        if failed.get() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Shifts `self` in by exactly the number of variables `binder` introduces, e.g. when
+    /// moving a parameter built outside `binder` into a context nested underneath it.
+    fn shift_through<U>(&self, binder: &Binder<U>) -> Self {
+        self.shift_in_by(binder.len())
+    }
+
+    /// Invokes `op` once for each bound variable in `self` (`debruijn: Some(_)`), passing
+    /// the de Bruijn depth it was found at, and rebuilds `self` with it replaced by
+    /// whatever `op` returns. A free variable (`debruijn: None`) is left untouched, same as
+    /// `Binder::instantiate`. Lets callers like decanonicalization write a flat replacement
+    /// closure instead of hand-rolling the shift-out/shift-in dance themselves.
+    fn fold_free_vars(&self, op: impl FnMut(ParameterKind, &BoundVar, DebruijnIndex) -> Parameter) -> Self
+    where
+        Self: Sized,
+    {
+        let op = RefCell::new(op);
+        self.substitute(&mut |kind, variable| match variable {
+            Variable::BoundVar(
+                bound_var @ BoundVar {
+                    debruijn: Some(depth),
+                    ..
+                },
+            ) => Some((op.borrow_mut())(kind, bound_var, *depth)),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::VarIndex;
+
+    /// A minimal `Fold` leaf wrapping a single variable, just enough to exercise the
+    /// default methods on `Fold` above without pulling in a real grammar term.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct OneVariable(Variable);
+
+    impl Fold for OneVariable {
+        fn substitute(&self, substitution_fn: SubstitutionFn<'_>) -> Self {
+            let _ = substitution_fn(ParameterKind::Ty, &self.0);
+            self.clone()
+        }
+
+        fn free_variables(&self) -> Vec<Variable> {
+            vec![self.0.clone()]
+        }
+
+        fn shift_in(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    fn bound(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: Some(DebruijnIndex::INNERMOST),
+            var_index: VarIndex { index },
+        })
+    }
+
+    fn free(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: None,
+            var_index: VarIndex { index },
+        })
+    }
+
+    #[test]
+    fn fold_free_vars_skips_free_variables() {
+        let term = OneVariable(free(0));
+
+        let mut seen = None;
+        term.fold_free_vars(|_kind, bound_var, depth| {
+            seen = Some((bound_var.clone(), depth));
+            bound_var.clone().into_parameter(ParameterKind::Ty)
+        });
+
+        assert!(
+            seen.is_none(),
+            "a variable with debruijn: None is free, not bound, and must not reach the callback"
+        );
+    }
+
+    #[test]
+    fn fold_free_vars_visits_bound_variables() {
+        let term = OneVariable(bound(3));
+
+        let mut seen = None;
+        term.fold_free_vars(|_kind, bound_var, depth| {
+            seen = Some((bound_var.var_index, depth));
+            bound_var.clone().into_parameter(ParameterKind::Ty)
+        });
+
+        assert_eq!(seen, Some((VarIndex { index: 3 }, DebruijnIndex::INNERMOST)));
+    }
+
+    /// A `Fold` leaf with nothing to do with variables at all, just for counting how many
+    /// times the default `shift_in_by` calls `shift_in`.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Counter(u32);
+
+    impl Fold for Counter {
+        fn substitute(&self, _substitution_fn: SubstitutionFn<'_>) -> Self {
+            self.clone()
+        }
+
+        fn free_variables(&self) -> Vec<Variable> {
+            vec![]
+        }
+
+        fn shift_in(&self) -> Self {
+            Counter(self.0 + 1)
+        }
+    }
+
+    #[test]
+    fn shift_in_by_applies_shift_in_the_given_number_of_times() {
+        assert_eq!(Counter(0).shift_in_by(3), Counter(3));
+        assert_eq!(Counter(0).shift_in_by(0), Counter(0));
+    }
+
+    #[test]
+    fn shift_out_by_fails_when_a_variable_would_escape() {
+        // Bound at the innermost binder: shifting out by even one level has nowhere for
+        // it to go.
+        let term = OneVariable(bound(0));
+        assert!(term.shift_out_by(1).is_none());
+    }
+
+    #[test]
+    fn shift_out_by_succeeds_for_a_free_variable() {
+        // Not relative to any binder, so removing enclosing binders can't affect it.
+        let term = OneVariable(free(0));
+        assert!(term.shift_out_by(1).is_some());
+    }
+}
@@ -9,9 +9,11 @@ use crate::{
     fold::Fold,
     fold::SubstitutionFn,
     grammar::VarIndex,
+    visit::Visit,
 };
 
 use super::{
+    validate::{BinderValidationError, ValidateBoundVars},
     BoundVar, DebruijnIndex, KindedVarIndex, Parameter, ParameterKind, Substitution, Variable,
 };
 
@@ -22,6 +24,48 @@ pub struct Binder<T> {
 }
 
 impl<T: Fold> Binder<T> {
+    pub fn into<U>(self) -> Binder<U>
+    where
+        T: Into<U>,
+    {
+        Binder {
+            kinds: self.kinds,
+            term: self.term.into(),
+        }
+    }
+
+    /// Number of variables bound by this binder
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    /// Instantiate the term, replacing each bound variable with `op(i)`.
+    pub fn instantiate(&self, mut op: impl FnMut(ParameterKind, VarIndex) -> Parameter) -> T {
+        let substitution: Vec<Parameter> = self
+            .kinds
+            .iter()
+            .zip(0..)
+            .map(|(&kind, index)| op(kind, VarIndex { index }))
+            .collect();
+
+        self.term.substitute(&mut |_kind, var| match var {
+            Variable::BoundVar(BoundVar {
+                debruijn: Some(DebruijnIndex::INNERMOST),
+                var_index,
+            }) => Some(substitution[var_index.index as usize].clone()),
+
+            _ => None,
+        })
+    }
+
+    /// Accesses the data inside the binder. Use this for simple tests that extract data
+    /// that is independent of the bound variables. If that's not the case, use `open`.
+    pub fn peek(&self) -> &T {
+        &self.term
+    }
+}
+
+impl<T: Fold + Visit> Binder<T> {
     /// Accesses the contents of the binder.
     ///
     /// The variables inside will be renamed to fresh var indices
@@ -29,6 +73,12 @@ impl<T: Fold> Binder<T> {
     ///
     /// The expectation is that you will create a term and use `Binder::new`.
     pub fn open(&self) -> (Vec<KindedVarIndex>, T) {
+        debug_assert!(
+            self.validate().is_ok(),
+            "malformed binder: {:?}",
+            self.validate().unwrap_err(),
+        );
+
         let (var_kinds, substitution): (Vec<KindedVarIndex>, Substitution) = self
             .kinds
             .iter()
@@ -74,47 +124,50 @@ impl<T: Fold> Binder<T> {
             .unzip();
 
         let term = substitution.apply(&term);
-        Binder { kinds, term }
+        let binder = Binder { kinds, term };
+        debug_assert!(
+            binder.validate().is_ok(),
+            "malformed binder: {:?}",
+            binder.validate().unwrap_err(),
+        );
+        binder
     }
 
-    pub fn into<U>(self) -> Binder<U>
-    where
-        T: Into<U>,
-    {
-        Binder {
-            kinds: self.kinds,
-            term: self.term.into(),
-        }
+    /// Checks that every reference to this binder's own bound variables is in range and
+    /// used at the kind declared for it, returning every problem found (not just the
+    /// first), so a buggy rule gets a complete report instead of a mysterious panic the
+    /// next time the term is substituted into.
+    pub fn validate(&self) -> Result<(), Vec<BinderValidationError>> {
+        let mut validator = ValidateBoundVars::new(&self.kinds);
+        let _ = self.term.visit(&mut validator, DebruijnIndex::INNERMOST);
+        errors_to_result(validator.into_errors())
     }
 
-    /// Number of variables bound by this binder
-    pub fn len(&self) -> usize {
-        self.kinds.len()
+    /// Like [`Self::validate`], but also flags slots that are declared but never
+    /// referenced anywhere inside the term.
+    pub fn validate_no_unused_slots(&self) -> Result<(), Vec<BinderValidationError>> {
+        let mut validator = ValidateBoundVars::new(&self.kinds);
+        let _ = self.term.visit(&mut validator, DebruijnIndex::INNERMOST);
+        errors_to_result(validator.check_unused_slots())
     }
+}
 
-    /// Instantiate the term, replacing each bound variable with `op(i)`.
-    pub fn instantiate(&self, mut op: impl FnMut(ParameterKind, VarIndex) -> Parameter) -> T {
-        let substitution: Vec<Parameter> = self
-            .kinds
-            .iter()
-            .zip(0..)
-            .map(|(&kind, index)| op(kind, VarIndex { index }))
-            .collect();
-
-        self.term.substitute(&mut |_kind, var| match var {
-            Variable::BoundVar(BoundVar {
-                debruijn: Some(DebruijnIndex::INNERMOST),
-                var_index,
-            }) => Some(substitution[var_index.index as usize].clone()),
-
-            _ => None,
-        })
+fn errors_to_result(errors: Vec<BinderValidationError>) -> Result<(), Vec<BinderValidationError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
+}
 
-    /// Accesses the data inside the binder. Use this for simple tests that extract data
-    /// that is independent of the bound variables. If that's not the case, use `open`.
-    pub fn peek(&self) -> &T {
-        &self.term
+impl<T> Binder<T> {
+    /// Creates a binder directly from a `term` that is already closed with respect to
+    /// `kinds` (every innermost `BoundVar` in `term` is in range and of the matching kind).
+    /// Most callers should go through [`Binder::new`]; this is for code, like
+    /// canonicalization, that builds the substitution itself as part of a single fold
+    /// over the term rather than via a list of existing variables.
+    pub(crate) fn new_closed(kinds: Vec<ParameterKind>, term: T) -> Self {
+        Binder { kinds, term }
     }
 }
 
@@ -168,6 +221,22 @@ impl<T: Fold> Fold for Binder<T> {
             term,
         }
     }
+
+    fn shift_in_by(&self, amount: usize) -> Self {
+        let term = self.term.shift_in_by(amount);
+        Binder {
+            kinds: self.kinds.clone(),
+            term,
+        }
+    }
+
+    fn shift_out_by(&self, amount: usize) -> Option<Self> {
+        let term = self.term.shift_out_by(amount)?;
+        Some(Binder {
+            kinds: self.kinds.clone(),
+            term,
+        })
+    }
 }
 
 impl<T, U> UpcastFrom<Binder<T>> for Binder<U>
@@ -183,3 +252,168 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use crate::visit::Visitor;
+
+    use super::*;
+
+    /// A single `BoundVar`, used at an explicitly chosen kind and depth, so tests can
+    /// construct a mismatch between that kind and a binder's declared `kinds`, or a
+    /// variable that escapes when shifted out by a given amount, without needing
+    /// `Variable` to carry a kind of its own.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct OneBoundVar {
+        kind: ParameterKind,
+        debruijn: DebruijnIndex,
+        index: usize,
+    }
+
+    impl OneBoundVar {
+        fn innermost(kind: ParameterKind, index: usize) -> Self {
+            OneBoundVar {
+                kind,
+                debruijn: DebruijnIndex::INNERMOST,
+                index,
+            }
+        }
+
+        fn variable(&self) -> Variable {
+            Variable::BoundVar(BoundVar {
+                debruijn: Some(self.debruijn),
+                var_index: VarIndex { index: self.index },
+            })
+        }
+    }
+
+    impl Fold for OneBoundVar {
+        fn substitute(&self, substitution_fn: SubstitutionFn<'_>) -> Self {
+            let _ = substitution_fn(self.kind, &self.variable());
+            self.clone()
+        }
+
+        fn free_variables(&self) -> Vec<Variable> {
+            vec![]
+        }
+
+        fn shift_in(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    impl Visit for OneBoundVar {
+        fn visit<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            outer_binder: DebruijnIndex,
+        ) -> ControlFlow<V::BreakTy> {
+            visitor.visit_variable(self.kind, &self.variable(), outer_binder)
+        }
+    }
+
+    #[test]
+    fn validate_reports_index_out_of_range() {
+        let term = OneBoundVar::innermost(ParameterKind::Ty, 1);
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], term);
+
+        assert_eq!(
+            binder.validate(),
+            Err(vec![BinderValidationError::IndexOutOfRange { index: 1, len: 1 }])
+        );
+    }
+
+    #[test]
+    fn validate_reports_kind_mismatch() {
+        let term = OneBoundVar::innermost(ParameterKind::Lifetime, 0);
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], term);
+
+        assert_eq!(
+            binder.validate(),
+            Err(vec![BinderValidationError::KindMismatch {
+                index: 0,
+                declared: ParameterKind::Ty,
+                used_as: ParameterKind::Lifetime,
+            }])
+        );
+    }
+
+    #[test]
+    fn validate_no_unused_slots_reports_a_slot_never_referenced() {
+        let term = OneBoundVar::innermost(ParameterKind::Ty, 0);
+        let binder = Binder::new_closed(vec![ParameterKind::Ty, ParameterKind::Ty], term);
+
+        assert!(binder.validate().is_ok());
+        assert_eq!(
+            binder.validate_no_unused_slots(),
+            Err(vec![BinderValidationError::UnusedSlot { index: 1 }])
+        );
+    }
+
+    /// A leaf that tracks how many times it's been shifted in, just enough to confirm
+    /// `Binder`'s own `shift_in_by` override passes the amount straight through to the
+    /// term in one step rather than going through the trait-default per-step loop.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct ShiftCounter(u32);
+
+    impl Fold for ShiftCounter {
+        fn substitute(&self, _substitution_fn: SubstitutionFn<'_>) -> Self {
+            self.clone()
+        }
+
+        fn free_variables(&self) -> Vec<Variable> {
+            vec![]
+        }
+
+        fn shift_in(&self) -> Self {
+            ShiftCounter(self.0 + 1)
+        }
+    }
+
+    #[test]
+    fn binder_shift_in_by_threads_the_amount_through_to_the_term() {
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], ShiftCounter(0));
+
+        let shifted = binder.shift_in_by(3);
+
+        assert_eq!(shifted.len(), binder.len());
+        assert_eq!(*shifted.peek(), ShiftCounter(3));
+    }
+
+    #[test]
+    fn binder_shift_out_by_fails_when_a_bound_variable_would_escape() {
+        // Refers to the binder directly enclosing this one, so removing that one level
+        // leaves it nowhere to go.
+        let term = OneBoundVar {
+            kind: ParameterKind::Ty,
+            debruijn: DebruijnIndex::INNERMOST.shift_in(),
+            index: 0,
+        };
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], term);
+
+        assert!(binder.shift_out_by(1).is_none());
+    }
+
+    #[test]
+    fn binder_shift_out_by_succeeds_when_the_variable_is_deep_enough() {
+        let term = OneBoundVar {
+            kind: ParameterKind::Ty,
+            debruijn: DebruijnIndex::INNERMOST.shift_in().shift_in(),
+            index: 0,
+        };
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], term);
+
+        let shifted = binder.shift_out_by(1).unwrap();
+        assert_eq!(shifted.len(), binder.len());
+    }
+
+    #[test]
+    fn shift_through_shifts_in_by_the_binder_len() {
+        let inner = Binder::new_closed(vec![ParameterKind::Ty, ParameterKind::Lifetime], ShiftCounter(0));
+        let term = ShiftCounter(0);
+
+        assert_eq!(term.shift_through(&inner), term.shift_in_by(2));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::fold::Fold;
+
+use super::{Binder, BoundVar, DebruijnIndex, Parameter, ParameterKind, VarIndex, Variable};
+
+/// A value whose free variables have all been replaced by bound variables, numbered in
+/// the order they were first encountered. Two terms that are alpha-and-inference-equivalent
+/// canonicalize to equal `Canonical` values, so they can be compared (or used as a hash map
+/// key) without any special-cased equivalence check.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Canonical<T> {
+    /// The kind of each variable that got bound, in the order it was first encountered.
+    pub binders: Vec<ParameterKind>,
+
+    /// The term, closed with respect to `binders`.
+    pub value: Binder<T>,
+}
+
+impl<T: Fold> Canonical<T> {
+    /// Opens the canonical value, replacing each bound variable with a fresh parameter
+    /// supplied by `op`, which is given the kind and index of the binder it instantiates.
+    pub fn instantiate_with(&self, op: impl FnMut(ParameterKind, VarIndex) -> Parameter) -> T {
+        self.value.instantiate(op)
+    }
+}
+
+/// Replaces the free variables of `value` with bound variables, assigning each distinct
+/// free variable the next index (`0..N`) in the order it is first encountered during a
+/// single fold over the term.
+pub fn canonicalize<T: Fold>(value: &T) -> Canonical<T> {
+    let mut binders: Vec<ParameterKind> = vec![];
+    let mut assigned: HashMap<Variable, Parameter> = HashMap::new();
+
+    let term = value.substitute(&mut |kind, variable| {
+        let parameter = assigned.entry(variable.clone()).or_insert_with(|| {
+            let var_index = VarIndex {
+                index: binders.len(),
+            };
+            binders.push(kind);
+            let bound_var = BoundVar {
+                debruijn: Some(DebruijnIndex::INNERMOST),
+                var_index,
+            };
+            bound_var.into_parameter(kind)
+        });
+        Some(parameter.clone())
+    });
+
+    let value = Binder::new_closed(binders.clone(), term);
+    Canonical { binders, value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_var(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: None,
+            var_index: VarIndex { index },
+        })
+    }
+
+    #[test]
+    fn canonicalize_binds_the_free_variable_at_its_own_kind() {
+        let original = free_var(5);
+        let canonical = canonicalize(&original);
+
+        assert_eq!(canonical.binders, vec![original.kind()]);
+        assert_eq!(canonical.value.len(), 1);
+    }
+
+    #[test]
+    fn canonicalize_dedups_a_repeated_variable_and_orders_distinct_ones_by_first_encounter() {
+        let x = free_var(5);
+        let y = free_var(9);
+        let canonical = canonicalize(&vec![y.clone(), x.clone(), y.clone()]);
+
+        assert_eq!(canonical.binders, vec![y.kind(), x.kind()]);
+        assert_eq!(
+            canonical.value.peek(),
+            &vec![
+                BoundVar {
+                    debruijn: Some(DebruijnIndex::INNERMOST),
+                    var_index: VarIndex { index: 0 },
+                }
+                .into_parameter(y.kind()),
+                BoundVar {
+                    debruijn: Some(DebruijnIndex::INNERMOST),
+                    var_index: VarIndex { index: 1 },
+                }
+                .into_parameter(x.kind()),
+                BoundVar {
+                    debruijn: Some(DebruijnIndex::INNERMOST),
+                    var_index: VarIndex { index: 0 },
+                }
+                .into_parameter(y.kind()),
+            ]
+        );
+    }
+
+    #[test]
+    fn canonicalize_equates_terms_that_differ_only_in_which_free_variables_they_use() {
+        let a = vec![free_var(1), free_var(2), free_var(1)];
+        let b = vec![free_var(7), free_var(3), free_var(7)];
+
+        assert_eq!(canonicalize(&a), canonicalize(&b));
+    }
+
+    #[test]
+    fn canonicalize_then_instantiate_with_round_trips() {
+        let original = free_var(5);
+        let canonical = canonicalize(&original);
+
+        let mut calls = vec![];
+        let result = canonical.instantiate_with(|kind, var_index| {
+            calls.push((kind, var_index));
+            BoundVar {
+                debruijn: None,
+                var_index: VarIndex { index: 99 },
+            }
+            .into_parameter(kind)
+        });
+
+        assert_eq!(calls, vec![(original.kind(), VarIndex { index: 0 })]);
+        assert_eq!(result, free_var(99));
+    }
+}
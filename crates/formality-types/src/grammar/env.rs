@@ -0,0 +1,216 @@
+use crate::fold::Fold;
+
+use super::{Binder, BoundVar, Parameter, ParameterKind, VarIndex};
+
+/// Identifies a binder by counting outward from the bottom of an [`Environment`]'s stack,
+/// rather than inward from the occurrence like a [`DebruijnIndex`] does, so a level stays
+/// stable as further binders are entered around it.
+///
+/// [`DebruijnIndex`]: super::DebruijnIndex
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Level(usize);
+
+impl Level {
+    /// Refers to this level as a parameter, for opening a binder against it.
+    fn into_parameter(self, kind: ParameterKind) -> Parameter {
+        BoundVar {
+            debruijn: None,
+            var_index: VarIndex { index: self.0 },
+        }
+        .into_parameter(kind)
+    }
+}
+
+/// One entry in an [`Environment`]: either a bound variable left abstract (identified by
+/// its `Level`) or one that has since been replaced by a concrete parameter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EnvItem {
+    Kept(Level),
+    Replaced(Parameter),
+}
+
+impl EnvItem {
+    fn into_parameter(&self, kind: ParameterKind) -> Parameter {
+        match self {
+            EnvItem::Kept(level) => level.into_parameter(kind),
+            EnvItem::Replaced(parameter) => parameter.clone(),
+        }
+    }
+}
+
+/// A lazily-extended substitution, NBE-style: entering a binder pushes one `Kept` entry
+/// per variable instead of eagerly rewriting the term, so entering costs time in the size
+/// of the environment (which `enter` copies) rather than in the size of the term.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Environment {
+    /// Every slot ever entered, in order, addressable by `Level`.
+    items: Vec<EnvItem>,
+
+    /// The starting offset into `items` of each binder entered so far, outermost first, so
+    /// a de Bruijn depth can be turned into the block of `items` it refers to.
+    frames: Vec<usize>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            items: vec![],
+            frames: vec![],
+        }
+    }
+
+    /// Enters `binder`, pushing one fresh `Kept(level)` entry per variable it binds without
+    /// looking at its term. Returns the extended environment along with the assigned levels.
+    pub fn enter<T>(&self, binder: &Binder<T>) -> (Environment, Vec<Level>) {
+        let base = self.items.len();
+        let levels: Vec<Level> = (0..binder.len()).map(|i| Level(base + i)).collect();
+
+        let mut items = self.items.clone();
+        items.extend(levels.iter().map(|&level| EnvItem::Kept(level)));
+
+        let mut frames = self.frames.clone();
+        frames.push(base);
+
+        (Environment { items, frames }, levels)
+    }
+
+    /// Resolves `bound_var` against the binders entered so far. `bound_var.debruijn` is
+    /// relative to the current walk, the same convention [`Fold::fold_free_vars`] reports
+    /// it in. Returns `None` if it's free with respect to every binder entered so far.
+    ///
+    /// [`Fold::fold_free_vars`]: crate::fold::Fold::fold_free_vars
+    pub fn resolve(&self, bound_var: &BoundVar) -> Option<&EnvItem> {
+        let depth = bound_var.debruijn?.index();
+        let frame = self.frames.len().checked_sub(depth + 1)?;
+        self.items.get(self.frames[frame] + bound_var.var_index.index)
+    }
+
+    /// Replaces the entry for `level` with a concrete parameter, so future resolutions of
+    /// that variable return it instead of the abstract level.
+    pub fn replace(&mut self, level: Level, parameter: Parameter) {
+        if let Some(item) = self
+            .items
+            .iter_mut()
+            .find(|item| matches!(item, EnvItem::Kept(l) if *l == level))
+        {
+            *item = EnvItem::Replaced(parameter);
+        }
+    }
+}
+
+/// Opens `binder` against `env`, resolving its variables on demand via
+/// [`Environment::resolve`]. Returns the extended environment together with the opened
+/// term. This visits every occurrence of `binder`'s variables, so unlike
+/// [`Environment::enter`] it costs `O(term size)`; call `enter` directly if only the
+/// assigned levels are needed.
+pub fn open_in_env<T: Fold>(binder: &Binder<T>, env: &Environment) -> (Environment, T) {
+    let (env, _levels) = env.enter(binder);
+
+    let term = binder.peek().fold_free_vars(|kind, bound_var, _depth| {
+        match env.resolve(bound_var) {
+            Some(item) => item.into_parameter(kind),
+            None => bound_var.clone().into_parameter(kind),
+        }
+    });
+
+    (env, term)
+}
+
+/// Compares two binders for alpha-equivalence by opening both against the same fresh
+/// levels and checking the results for structural equality. Each term is rebuilt once by
+/// [`open_in_env`], with its own bound variables replaced by those levels.
+pub fn alpha_equivalent<T: Fold + PartialEq>(a: &Binder<T>, b: &Binder<T>) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let env = Environment::new();
+    let (env_a, a_term) = open_in_env(a, &env);
+    let (_, b_term) = open_in_env(b, &env);
+    debug_assert_eq!(env_a.items.len(), a.len());
+
+    a_term == b_term
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::{DebruijnIndex, ParameterKind, Variable};
+
+    fn bound(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: Some(DebruijnIndex::INNERMOST),
+            var_index: VarIndex { index },
+        })
+    }
+
+    fn free(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: None,
+            var_index: VarIndex { index },
+        })
+    }
+
+    #[test]
+    fn alpha_equivalent_treats_consistently_renamed_bound_variables_as_equal() {
+        let a = Binder::new_closed(vec![ParameterKind::Ty], vec![bound(0)]);
+        let b = Binder::new_closed(vec![ParameterKind::Ty], vec![bound(0)]);
+
+        assert!(alpha_equivalent(&a, &b));
+    }
+
+    #[test]
+    fn alpha_equivalent_does_not_conflate_a_free_variable_with_a_bound_one() {
+        // Regression test: `fold_free_vars` used to default a free variable's depth to
+        // `INNERMOST` via `unwrap_or`, which made `open_in_env` treat it as a reference to
+        // the binder being opened instead of leaving it alone.
+        let a = Binder::new_closed(vec![ParameterKind::Ty], vec![bound(0), free(7)]);
+        let b = Binder::new_closed(vec![ParameterKind::Ty], vec![bound(0), free(8)]);
+
+        assert!(!alpha_equivalent(&a, &b));
+    }
+
+    /// A reference to the outer binder's variable, as seen from one level further in
+    /// (`debruijn` counts binders innermost first, so this is `DebruijnIndex` 1).
+    fn outer_bound(index: usize) -> Variable {
+        Variable::BoundVar(BoundVar {
+            debruijn: Some(DebruijnIndex::INNERMOST.shift_in()),
+            var_index: VarIndex { index },
+        })
+    }
+
+    #[test]
+    fn alpha_equivalent_resolves_references_to_an_enclosing_binder() {
+        // Two levels deep: the inner binder's term refers back to the outer binder's
+        // variable. Opening the outer binder resolves that reference to the outer level;
+        // opening the inner one then pushes a second frame on top of the first.
+        let inner_a = Binder::new_closed(vec![ParameterKind::Ty], vec![outer_bound(0)]);
+        let inner_b = Binder::new_closed(vec![ParameterKind::Ty], vec![outer_bound(0)]);
+        let a = Binder::new_closed(vec![ParameterKind::Ty], inner_a);
+        let b = Binder::new_closed(vec![ParameterKind::Ty], inner_b);
+
+        let env = Environment::new();
+        let (env_a, inner_a) = open_in_env(&a, &env);
+        let (env_b, inner_b) = open_in_env(&b, &env);
+        let (env_a, a_term) = open_in_env(&inner_a, &env_a);
+        let (_, b_term) = open_in_env(&inner_b, &env_b);
+
+        assert_eq!(env_a.frames.len(), 2);
+        assert_eq!(a_term, b_term);
+    }
+
+    #[test]
+    fn replace_overrides_how_a_kept_level_resolves() {
+        let binder = Binder::new_closed(vec![ParameterKind::Ty], vec![bound(0)]);
+
+        let (mut env, levels) = Environment::new().enter(&binder);
+        let replacement = free(99);
+        env.replace(levels[0], replacement.clone());
+
+        let resolved = env.resolve(&BoundVar {
+            debruijn: Some(DebruijnIndex::INNERMOST),
+            var_index: VarIndex { index: 0 },
+        });
+        assert_eq!(resolved, Some(&EnvItem::Replaced(replacement)));
+    }
+}
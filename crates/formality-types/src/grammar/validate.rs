@@ -0,0 +1,94 @@
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+
+use crate::visit::Visitor;
+
+use super::{BoundVar, DebruijnIndex, ParameterKind, Variable};
+
+/// A problem found by [`super::Binder::validate`]: the `kinds` a binder declares don't
+/// match how its bound variables are actually used inside the term.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BinderValidationError {
+    /// A bound variable referenced an index past the end of `kinds`.
+    IndexOutOfRange { index: usize, len: usize },
+
+    /// A bound variable was used at a different kind than `kinds` declares for its index.
+    KindMismatch {
+        index: usize,
+        declared: ParameterKind,
+        used_as: ParameterKind,
+    },
+
+    /// A slot in `kinds` is never referenced anywhere inside the term.
+    UnusedSlot { index: usize },
+}
+
+/// Walks a binder's term collecting every reference to the binder being validated
+/// (an innermost `BoundVar`, relative to the depth at which the walk started), checking
+/// each one against the declared `kinds`.
+pub(crate) struct ValidateBoundVars<'k> {
+    kinds: &'k [ParameterKind],
+    used: Vec<bool>,
+    errors: Vec<BinderValidationError>,
+}
+
+impl<'k> ValidateBoundVars<'k> {
+    pub(crate) fn new(kinds: &'k [ParameterKind]) -> Self {
+        ValidateBoundVars {
+            kinds,
+            used: vec![false; kinds.len()],
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn check_unused_slots(mut self) -> Vec<BinderValidationError> {
+        for (index, used) in self.used.iter().enumerate() {
+            if !used {
+                self.errors.push(BinderValidationError::UnusedSlot { index });
+            }
+        }
+        self.errors
+    }
+
+    pub(crate) fn into_errors(self) -> Vec<BinderValidationError> {
+        self.errors
+    }
+}
+
+impl<'k> Visitor for ValidateBoundVars<'k> {
+    type BreakTy = Infallible;
+
+    fn visit_variable(
+        &mut self,
+        kind: ParameterKind,
+        variable: &Variable,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<Infallible> {
+        if let Variable::BoundVar(BoundVar {
+            debruijn: Some(debruijn),
+            var_index,
+        }) = variable
+        {
+            if *debruijn == outer_binder {
+                let index = var_index.index;
+                match self.kinds.get(index) {
+                    None => self.errors.push(BinderValidationError::IndexOutOfRange {
+                        index,
+                        len: self.kinds.len(),
+                    }),
+                    Some(&declared) => {
+                        self.used[index] = true;
+                        if declared != kind {
+                            self.errors.push(BinderValidationError::KindMismatch {
+                                index,
+                                declared,
+                                used_as: kind,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
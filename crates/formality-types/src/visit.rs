@@ -0,0 +1,149 @@
+//! Parallel to [`Fold`](crate::fold::Fold), but for code that only needs to look at a
+//! term rather than rebuild it. `Visit` never allocates a `Vec` for the whole term and can
+//! stop walking as soon as the visitor has seen enough.
+
+use std::ops::ControlFlow;
+
+use crate::fold::Fold;
+use crate::grammar::{Binder, BoundVar, DebruijnIndex, ParameterKind, Variable};
+
+pub trait Visit {
+    /// Walks `self`, reporting each `Variable` to `visitor` along with the kind it's used
+    /// at. `outer_binder` is the de Bruijn depth of the binders that already enclose
+    /// `self`; entering a `Binder` increments it by one before recursing into the term, so
+    /// visitors can tell a variable bound within `self` from one that's free with respect
+    /// to it.
+    fn visit<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<V::BreakTy>;
+}
+
+/// Receives callbacks from a [`Visit`] walk.
+pub trait Visitor {
+    /// The value produced when the walk short-circuits.
+    type BreakTy;
+
+    /// Called once for each `Variable` encountered during the walk.
+    fn visit_variable(
+        &mut self,
+        kind: ParameterKind,
+        variable: &Variable,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<Self::BreakTy>;
+}
+
+impl Visit for Variable {
+    fn visit<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<V::BreakTy> {
+        visitor.visit_variable(self.kind(), self, outer_binder)
+    }
+}
+
+impl<T: Fold + Visit> Visit for Binder<T> {
+    fn visit<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<V::BreakTy> {
+        self.peek().visit(visitor, outer_binder.shift_in())
+    }
+}
+
+impl<T: Visit> Visit for Vec<T> {
+    fn visit<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<V::BreakTy> {
+        for item in self {
+            item.visit(visitor, outer_binder)?;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl<T: Visit> Visit for Option<T> {
+    fn visit<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<V::BreakTy> {
+        match self {
+            Some(item) => item.visit(visitor, outer_binder),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// An occurs-check: does `variable` appear free anywhere in `term`? Unlike
+/// `Fold::free_variables`, this stops at the first match instead of collecting every free
+/// variable in the term.
+pub fn occurs_in<T: Visit>(variable: &Variable, term: &T) -> bool {
+    struct OccursCheck<'v> {
+        target: &'v Variable,
+    }
+
+    impl<'v> Visitor for OccursCheck<'v> {
+        type BreakTy = ();
+
+        fn visit_variable(
+            &mut self,
+            _kind: ParameterKind,
+            variable: &Variable,
+            outer_binder: DebruijnIndex,
+        ) -> ControlFlow<()> {
+            // Under `outer_binder` binders, a free occurrence of the target carries a de
+            // Bruijn index shifted in once per binder crossed.
+            if *variable == self.target.shift_in_by(outer_binder.index()) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    term.visit(&mut OccursCheck { target: variable }, DebruijnIndex::INNERMOST)
+        .is_break()
+}
+
+/// Returns the first variable encountered in `term` that is bound *within* `term` itself
+/// (as opposed to one of `term`'s own free variables), or `None` if there isn't one.
+pub fn first_bound_variable<T: Visit>(term: &T) -> Option<Variable> {
+    struct BoundVarCollector {
+        found: Option<Variable>,
+    }
+
+    impl Visitor for BoundVarCollector {
+        type BreakTy = ();
+
+        fn visit_variable(
+            &mut self,
+            _kind: ParameterKind,
+            variable: &Variable,
+            outer_binder: DebruijnIndex,
+        ) -> ControlFlow<()> {
+            let bound_within_term = matches!(
+                variable,
+                Variable::BoundVar(BoundVar {
+                    debruijn: Some(d),
+                    ..
+                }) if *d < outer_binder
+            );
+            if bound_within_term {
+                self.found = Some(variable.clone());
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    let mut collector = BoundVarCollector { found: None };
+    let _ = term.visit(&mut collector, DebruijnIndex::INNERMOST);
+    collector.found
+}